@@ -0,0 +1,168 @@
+use crate::common::error::Error;
+use std::env;
+
+/// The action that `su` should take once argument parsing has finished.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SuAction {
+    Help,
+    Version,
+    Run,
+}
+
+/// Parsed command line options for `su`.
+#[derive(Debug)]
+pub struct SuOptions {
+    pub action: SuAction,
+    /// Start a login shell for the target user (`-`, `-l`, `--login`).
+    pub login: bool,
+    /// Keep the calling user's environment instead of resetting it (`-p`, `--preserve-environment`).
+    pub preserve_environment: bool,
+    /// Run the target command inside a freshly allocated pseudo-terminal (`--pty`).
+    pub pty: bool,
+    /// Extra environment variables to preserve verbatim from the caller's environment, on top of
+    /// the default safe allowlist (`-w`, `--whitelist-environment`).
+    pub whitelist_environment: Vec<String>,
+    /// Shell to run instead of the target user's login shell (`-s`, `--shell`).
+    pub shell: Option<String>,
+    /// The user to switch to, defaulting to root.
+    pub user: String,
+    /// Command (and arguments) to run instead of an interactive shell.
+    pub command: Vec<String>,
+}
+
+impl Default for SuOptions {
+    fn default() -> Self {
+        Self {
+            action: SuAction::Run,
+            login: false,
+            preserve_environment: false,
+            pty: false,
+            whitelist_environment: Vec::new(),
+            shell: None,
+            user: "root".to_string(),
+            command: Vec::new(),
+        }
+    }
+}
+
+impl SuOptions {
+    pub fn from_env() -> Result<Self, Error> {
+        Self::parse(env::args().skip(1))
+    }
+
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, Error> {
+        let mut options = Self::default();
+        let mut args = args.peekable();
+        let mut user_set = false;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => {
+                    options.action = SuAction::Help;
+                    return Ok(options);
+                }
+                "-V" | "--version" => {
+                    options.action = SuAction::Version;
+                    return Ok(options);
+                }
+                "-" | "-l" | "--login" => options.login = true,
+                "-p" | "-m" | "--preserve-environment" => options.preserve_environment = true,
+                "--pty" => options.pty = true,
+                "-w" | "--whitelist-environment" => {
+                    let names = args.next().ok_or_else(|| {
+                        Error::InvalidCommand("--whitelist-environment requires an argument".into())
+                    })?;
+                    options
+                        .whitelist_environment
+                        .extend(names.split(',').map(str::to_string));
+                }
+                "-s" | "--shell" => {
+                    let shell = args
+                        .next()
+                        .ok_or_else(|| Error::InvalidCommand("-s requires an argument".into()))?;
+                    options.shell = Some(shell);
+                }
+                "-c" | "--command" => {
+                    let command = args
+                        .next()
+                        .ok_or_else(|| Error::InvalidCommand("-c requires an argument".into()))?;
+                    options.command.push(command);
+                }
+                _ if !user_set && !arg.starts_with('-') => {
+                    options.user = arg;
+                    user_set = true;
+                    options.command.extend(args);
+                    break;
+                }
+                other => {
+                    return Err(Error::InvalidCommand(format!("unrecognized option '{other}'")));
+                }
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<SuOptions, Error> {
+        SuOptions::parse(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn defaults_to_root_with_no_arguments() {
+        let options = parse(&[]).unwrap();
+        assert_eq!(options.user, "root");
+        assert_eq!(options.action, SuAction::Run);
+        assert!(!options.pty);
+        assert!(options.whitelist_environment.is_empty());
+    }
+
+    #[test]
+    fn pty_flag_is_recognized() {
+        let options = parse(&["--pty", "alice"]).unwrap();
+        assert!(options.pty);
+        assert_eq!(options.user, "alice");
+    }
+
+    #[test]
+    fn whitelist_environment_splits_on_commas() {
+        let options = parse(&["-w", "FOO,BAR", "alice"]).unwrap();
+        assert_eq!(
+            options.whitelist_environment,
+            vec!["FOO".to_string(), "BAR".to_string()]
+        );
+    }
+
+    #[test]
+    fn whitelist_environment_accumulates_across_repeated_uses() {
+        let options = parse(&["-w", "FOO", "--whitelist-environment", "BAR", "alice"]).unwrap();
+        assert_eq!(
+            options.whitelist_environment,
+            vec!["FOO".to_string(), "BAR".to_string()]
+        );
+    }
+
+    #[test]
+    fn whitelist_environment_without_an_argument_is_an_error() {
+        assert!(parse(&["-w"]).is_err());
+    }
+
+    #[test]
+    fn shell_without_an_argument_is_an_error() {
+        assert!(parse(&["-s"]).is_err());
+    }
+
+    #[test]
+    fn trailing_arguments_after_the_user_become_the_command() {
+        let options = parse(&["alice", "ls", "-la"]).unwrap();
+        assert_eq!(options.user, "alice");
+        assert_eq!(
+            options.command,
+            vec!["ls".to_string(), "-la".to_string()]
+        );
+    }
+}
@@ -0,0 +1,307 @@
+use crate::common::error::Error;
+use crate::su::cli::SuOptions;
+use std::collections::HashMap;
+use std::ffi::{c_int, CStr, CString};
+use std::io;
+use std::mem::MaybeUninit;
+use std::ptr;
+
+pub type Environment = HashMap<String, String>;
+
+/// Passwd-derived information about the target user.
+#[derive(Debug, Clone)]
+pub struct User {
+    pub name: String,
+    pub uid: libc::uid_t,
+    pub gid: libc::gid_t,
+    /// The full supplementary group list (including `gid`), resolved via `getgrouplist` so that a
+    /// command run via `su` ends up with exactly the target user's groups instead of either
+    /// inheriting the caller's or only picking up `gid`.
+    pub groups: Vec<libc::gid_t>,
+    pub home: String,
+    pub shell: String,
+}
+
+/// Information about the process that will run the target command.
+#[derive(Debug)]
+pub struct ProcessContext {
+    pub pid: libc::pid_t,
+}
+
+/// Everything `su` needs to know in order to authenticate the target user and run a command (or
+/// shell) as them.
+#[derive(Debug)]
+pub struct SuContext {
+    user: User,
+    login: bool,
+    /// Run the command inside a freshly allocated pseudo-terminal, see [`SuOptions::pty`].
+    pty: bool,
+    pub process: ProcessContext,
+    pub command: Vec<String>,
+    pub environment: Environment,
+}
+
+impl SuContext {
+    pub fn from_env(options: SuOptions) -> Result<Self, Error> {
+        let user = lookup_user(&options.user)?;
+        let command = resolve_command(&options, &user);
+        let environment = build_environment(&options, &user);
+
+        Ok(Self {
+            login: options.login,
+            pty: options.pty,
+            command,
+            user,
+            process: ProcessContext {
+                pid: std::process::id() as libc::pid_t,
+            },
+            environment,
+        })
+    }
+
+    pub fn user(&self) -> &User {
+        &self.user
+    }
+
+    pub fn is_login(&self) -> bool {
+        self.login
+    }
+
+    /// Whether the command should be run inside a freshly allocated pseudo-terminal instead of
+    /// directly on `su`'s controlling terminal.
+    pub fn use_pty(&self) -> bool {
+        self.pty
+    }
+
+    /// Replace `su`'s own supplementary groups with the target user's, so that whichever exec
+    /// path runs next (the default fork in `crate::exec::run_command`, or `--pty`'s own `Command`)
+    /// inherits the right groups instead of the caller's. Must be called while `su` still has the
+    /// privileges to call `setgroups`, i.e. before any uid/gid switch.
+    pub fn apply_supplementary_groups(&self) -> io::Result<()> {
+        let result =
+            unsafe { libc::setgroups(self.user.groups.len(), self.user.groups.as_ptr()) };
+
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
+
+fn resolve_command(options: &SuOptions, user: &User) -> Vec<String> {
+    if !options.command.is_empty() {
+        options.command.clone()
+    } else {
+        vec![options.shell.clone().unwrap_or_else(|| user.shell.clone())]
+    }
+}
+
+/// Variables that are always safe to carry over from the caller, e.g. because they only affect
+/// how output is displayed rather than what gets run. Anything else (`LD_PRELOAD`, `IFS`,
+/// `BASH_ENV`, ...) is dropped unless the caller named it via `--whitelist-environment`.
+const SAFE_ENVIRONMENT_ALLOWLIST: &[&str] = &["TERM", "COLORTERM", "DISPLAY", "XAUTHORITY"];
+
+/// The default `PATH` used for a hardened session, mirroring `login.defs`' `ENV_SUPATH`/
+/// `ENV_PATH` distinction between root and non-root targets.
+const ROOT_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+const USER_PATH: &str = "/usr/local/bin:/usr/bin:/bin:/usr/local/games:/usr/games";
+
+fn build_environment(options: &SuOptions, user: &User) -> Environment {
+    if options.preserve_environment {
+        return std::env::vars().collect();
+    }
+
+    let mut environment = Environment::new();
+
+    // Carry over only the allowlisted variables (plus anything the caller explicitly
+    // whitelisted), so that dangerous inherited variables can't cross the privilege boundary.
+    let allowed = SAFE_ENVIRONMENT_ALLOWLIST
+        .iter()
+        .copied()
+        .chain(options.whitelist_environment.iter().map(String::as_str));
+    for name in allowed {
+        if let Ok(value) = std::env::var(name) {
+            environment.insert(name.to_string(), value);
+        }
+    }
+
+    environment.insert("HOME".to_string(), user.home.clone());
+    environment.insert("SHELL".to_string(), user.shell.clone());
+    environment.insert("USER".to_string(), user.name.clone());
+    environment.insert("LOGNAME".to_string(), user.name.clone());
+    environment.insert(
+        "PATH".to_string(),
+        if user.uid == 0 { ROOT_PATH } else { USER_PATH }.to_string(),
+    );
+
+    environment
+}
+
+fn lookup_user(name: &str) -> Result<User, Error> {
+    let c_name = CString::new(name).map_err(|_| Error::InvalidCommand(name.to_string()))?;
+
+    let mut passwd = MaybeUninit::<libc::passwd>::uninit();
+    let mut result = ptr::null_mut();
+    // `getpwnam_r` wants a scratch buffer that it may write arbitrarily many strings into; start
+    // small and grow if `ERANGE` is returned.
+    let mut buf = vec![0_i8; 1024];
+
+    loop {
+        let status = unsafe {
+            libc::getpwnam_r(
+                c_name.as_ptr(),
+                passwd.as_mut_ptr(),
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            )
+        };
+
+        if status == 0 {
+            break;
+        } else if status == libc::ERANGE {
+            buf.resize(buf.len() * 2, 0);
+        } else {
+            return Err(Error::from(std::io::Error::from_raw_os_error(status)));
+        }
+    }
+
+    if result.is_null() {
+        return Err(Error::InvalidCommand(format!("user '{name}' not found")));
+    }
+
+    let passwd = unsafe { passwd.assume_init() };
+
+    let home = unsafe { CStr::from_ptr(passwd.pw_dir) }
+        .to_string_lossy()
+        .into_owned();
+    let shell = unsafe { CStr::from_ptr(passwd.pw_shell) }
+        .to_string_lossy()
+        .into_owned();
+
+    let groups = lookup_groups(&c_name, passwd.pw_gid)?;
+
+    Ok(User {
+        name: name.to_string(),
+        uid: passwd.pw_uid,
+        gid: passwd.pw_gid,
+        groups,
+        home,
+        shell,
+    })
+}
+
+/// Resolve the full supplementary group list for `name` via `getgrouplist`, i.e. the same group
+/// list `id name` reports, rather than just the primary group from its passwd entry.
+fn lookup_groups(name: &CStr, gid: libc::gid_t) -> Result<Vec<libc::gid_t>, Error> {
+    // Start with a small buffer and retry with whatever size `getgrouplist` reports is needed;
+    // there is no portable upper bound on how many groups a user can belong to.
+    let mut ngroups: c_int = 16;
+
+    loop {
+        let mut groups = vec![0 as libc::gid_t; ngroups as usize];
+        let mut count = ngroups;
+
+        let result = unsafe {
+            libc::getgrouplist(name.as_ptr(), gid, groups.as_mut_ptr(), &mut count)
+        };
+
+        if result >= 0 {
+            groups.truncate(count as usize);
+            return Ok(groups);
+        }
+
+        // `getgrouplist` returns -1 and sets `count` to the required size when the buffer was too
+        // small.
+        ngroups = count;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_user() -> User {
+        User {
+            name: "alice".to_string(),
+            uid: 1000,
+            gid: 1000,
+            groups: vec![1000, 27],
+            home: "/home/alice".to_string(),
+            shell: "/bin/bash".to_string(),
+        }
+    }
+
+    #[test]
+    fn preserve_environment_keeps_the_full_caller_environment() {
+        std::env::set_var("SU_TEST_PRESERVE_VAR", "value");
+        let mut options = SuOptions::default();
+        options.preserve_environment = true;
+
+        let environment = build_environment(&options, &test_user());
+
+        assert_eq!(
+            environment.get("SU_TEST_PRESERVE_VAR").map(String::as_str),
+            Some("value")
+        );
+        std::env::remove_var("SU_TEST_PRESERVE_VAR");
+    }
+
+    #[test]
+    fn default_environment_drops_unsafe_inherited_variables() {
+        std::env::set_var("SU_TEST_LD_PRELOAD", "/evil.so");
+        let options = SuOptions::default();
+
+        let environment = build_environment(&options, &test_user());
+
+        assert!(!environment.contains_key("SU_TEST_LD_PRELOAD"));
+        assert_eq!(environment.get("HOME").map(String::as_str), Some("/home/alice"));
+        assert_eq!(environment.get("USER").map(String::as_str), Some("alice"));
+        assert_eq!(environment.get("LOGNAME").map(String::as_str), Some("alice"));
+        assert_eq!(environment.get("SHELL").map(String::as_str), Some("/bin/bash"));
+        std::env::remove_var("SU_TEST_LD_PRELOAD");
+    }
+
+    #[test]
+    fn default_environment_keeps_the_safe_allowlist() {
+        std::env::set_var("TERM", "xterm-256color");
+        let options = SuOptions::default();
+
+        let environment = build_environment(&options, &test_user());
+
+        assert_eq!(
+            environment.get("TERM").map(String::as_str),
+            Some("xterm-256color")
+        );
+        std::env::remove_var("TERM");
+    }
+
+    #[test]
+    fn whitelist_environment_adds_extra_variables_to_the_allowlist() {
+        std::env::set_var("SU_TEST_CUSTOM_VAR", "kept");
+        let mut options = SuOptions::default();
+        options.whitelist_environment = vec!["SU_TEST_CUSTOM_VAR".to_string()];
+
+        let environment = build_environment(&options, &test_user());
+
+        assert_eq!(
+            environment.get("SU_TEST_CUSTOM_VAR").map(String::as_str),
+            Some("kept")
+        );
+        std::env::remove_var("SU_TEST_CUSTOM_VAR");
+    }
+
+    #[test]
+    fn path_depends_on_whether_the_target_is_root() {
+        let options = SuOptions::default();
+
+        let mut root = test_user();
+        root.uid = 0;
+        let root_environment = build_environment(&options, &root);
+        assert_eq!(root_environment.get("PATH").map(String::as_str), Some(ROOT_PATH));
+
+        let non_root_environment = build_environment(&options, &test_user());
+        assert_eq!(non_root_environment.get("PATH").map(String::as_str), Some(USER_PATH));
+    }
+}
@@ -9,6 +9,7 @@ use context::SuContext;
 
 mod cli;
 mod context;
+mod pty;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -69,18 +70,32 @@ fn run(options: SuOptions) -> Result<(), Error> {
     // authenticate the target user
     let mut pam = authenticate(&context.user().name, context.is_login())?;
 
+    // Drop `su`'s own supplementary groups to the target user's before either exec path forks,
+    // so a command launched via `su` never retains the caller's groups.
+    context.apply_supplementary_groups()?;
+
     // run command and return corresponding exit code
     let environment = context.environment.clone();
     let pid = context.process.pid;
-
-    let (reason, emulate_default_handler) = crate::exec::run_command(context, environment)?;
-
-    // closing the pam session is best effort, if any error occurs we cannot
-    // do anything with it
-    let _ = pam.close_session();
-
-    // Run any clean-up code before this line.
-    emulate_default_handler();
+    let use_pty = context.use_pty();
+
+    let reason = if use_pty {
+        let reason = pty::run_pty(context, environment)?;
+        // closing the pam session is best effort, if any error occurs we cannot
+        // do anything with it
+        let _ = pam.close_session();
+        reason
+    } else {
+        let (reason, emulate_default_handler) = crate::exec::run_command(context, environment)?;
+
+        // closing the pam session is best effort, if any error occurs we cannot
+        // do anything with it
+        let _ = pam.close_session();
+
+        // Run any clean-up code before this line.
+        emulate_default_handler();
+        reason
+    };
 
     match reason {
         ExitReason::Code(code) => process::exit(code),
@@ -97,7 +112,19 @@ pub fn main() {
 
     match su_options.action {
         SuAction::Help => {
-            println!("Usage: su [options] [-] [<user> [<argument>...]]");
+            println!(
+                "Usage: su [options] [-] [<user> [<argument>...]]\n\
+                 \n\
+                 Options:\n\
+                 \x20\x20-c, --command <command>          run <command> directly instead of a shell\n\
+                 \x20\x20-s, --shell <shell>              run <shell> instead of the target user's login shell\n\
+                 \x20\x20-l, --login                      start a login shell\n\
+                 \x20\x20-p, -m, --preserve-environment   keep the caller's environment instead of resetting it\n\
+                 \x20\x20-w, --whitelist-environment <list>  comma-separated list of extra variables to keep\n\
+                 \x20\x20--pty                            run the command in a freshly allocated pseudo-terminal\n\
+                 \x20\x20-h, --help                       display this help and exit\n\
+                 \x20\x20-V, --version                    output version information and exit"
+            );
             std::process::exit(0);
         }
         SuAction::Version => {
@@ -0,0 +1,196 @@
+//! Runs the target command inside a freshly allocated pseudo-terminal (`su --pty`), relaying
+//! input and output between the real terminal and the PTY so that the child cannot inject events
+//! into (or receive job-control signals from) `su`'s own terminal.
+
+use std::io::{self, Read, Write};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Command, Stdio};
+
+use crate::common::error::Error;
+use crate::exec::ExitReason;
+use crate::su::context::SuContext;
+use crate::system::term::{open_pty, term_copy, term_raw, term_restore, term_set_winsize};
+
+/// Run `context.command` as the session leader of a new session, attached to the slave end of a
+/// freshly allocated pseudo-terminal, and relay data between it and the real terminal until the
+/// child exits.
+pub fn run_pty(context: SuContext, environment: crate::su::context::Environment) -> Result<ExitReason, Error> {
+    let stdin = io::stdin();
+    let pty = open_pty()?;
+
+    // Clone the real terminal's settings and window size onto the slave before the child attaches
+    // to it, so that e.g. `stty` reports the same thing inside and outside the session.
+    term_copy(&stdin, &pty.slave)?;
+
+    let slave_fd = pty.slave.try_clone()?;
+    let uid = context.user().uid;
+    let gid = context.user().gid;
+    let mut child = Command::new(&context.command[0])
+        .args(&context.command[1..])
+        // `Command` inherits `su`'s own environment by default; clear it first so the child only
+        // ever sees the sanitized `environment` built by `SuContext`, not `su`'s real environment
+        // layered underneath it.
+        .env_clear()
+        .envs(&environment)
+        .stdin(Stdio::from(pty.slave.try_clone()?))
+        .stdout(Stdio::from(pty.slave.try_clone()?))
+        .stderr(Stdio::from(slave_fd))
+        // SAFETY: only async-signal-safe functions (`setsid`, `ioctl`, `pthread_sigmask`,
+        // `setgid`, `setuid`) are called between `fork` and `exec`.
+        .pre_exec(move || {
+            // Detach from the controlling terminal inherited from `su` and make the slave the new
+            // controlling terminal of the freshly created session.
+            if unsafe { libc::setsid() } == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            if unsafe { libc::ioctl(0, libc::TIOCSCTTY as _, 0) } == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            // `block_sigwinch` below blocks `SIGWINCH` so our dedicated watcher thread can
+            // `sigwait` on it; undo that for the child so it reacts to its own `SIGWINCH` the
+            // way it normally would.
+            unblock_sigwinch();
+            // `su`'s own supplementary groups were already switched to the target user's via
+            // `SuContext::apply_supplementary_groups`, so the child inherits them. Switch uid/gid
+            // here rather than via `Command::uid`/`gid`: with `uid` set and no explicit group
+            // list, `Command` clears all supplementary groups before `setuid`.
+            if unsafe { libc::setgid(gid) } == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            if unsafe { libc::setuid(uid) } == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        })
+        .spawn()?;
+
+    // Drop our copy of the slave so that the master side sees EOF once the child (the only other
+    // holder of the slave) exits.
+    drop(pty.slave);
+
+    // Block `SIGWINCH` in this thread (and, since it is inherited, every thread we spawn after
+    // this point) so that only the dedicated watcher thread spawned in `relay` consumes it.
+    block_sigwinch();
+
+    let raw_mode = term_raw(io::stdin(), true)?;
+    let result = relay(&pty.master, &mut child);
+    term_restore(raw_mode, false)?;
+
+    let status = result?;
+
+    Ok(match status.signal() {
+        Some(signal) => ExitReason::Signal(signal),
+        None => ExitReason::Code(status.code().unwrap_or(1)),
+    })
+}
+
+/// Copy bytes between the PTY master and our own stdin/stdout until the child exits, and push
+/// any `SIGWINCH`-driven window size changes from `stdin` to `master`.
+fn relay(master: &std::fs::File, child: &mut std::process::Child) -> io::Result<std::process::ExitStatus> {
+    let mut master_reader = master.try_clone()?;
+    let mut master_writer = master.try_clone()?;
+
+    // Forward our stdin to the PTY master on a dedicated thread; the main thread forwards PTY
+    // output to our stdout and waits for the child.
+    let input_thread = std::thread::spawn(move || -> io::Result<()> {
+        let mut stdin = io::stdin();
+        let mut buf = [0_u8; 4096];
+        loop {
+            let n = stdin.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            master_writer.write_all(&buf[..n])?;
+        }
+        Ok(())
+    });
+
+    // `SIGWINCH` is blocked in every thread of this process (see `block_sigwinch` in `run_pty`),
+    // so this thread can pick each delivery off the pending-signals queue with `sigwait` instead
+    // of installing a signal handler, following the approach alacritty's PTY backend uses to
+    // re-push `TIOCSWINSZ` on resize.
+    let winsize_master = master.try_clone()?;
+    let _winsize_thread = std::thread::spawn(move || loop {
+        if !wait_for_sigwinch() {
+            break;
+        }
+        let _ = term_set_winsize(&io::stdin(), &winsize_master);
+    });
+
+    // Any error below stops the relay loop, but we still need to reap the child and report its
+    // real exit status; stash the error and return it only after `child.wait()` has run.
+    let mut relay_error = None;
+    // Our own stdout can go away independently of the child, e.g. when piped into something like
+    // `head` that exits early. Once that happens we stop writing to it, but we must keep draining
+    // `master` until the child is actually done: leaving it unread would let the child block on a
+    // full pty buffer, and `child.wait()` below would then hang forever waiting for a child that
+    // is itself stuck waiting for a reader.
+    let mut stdout_closed = false;
+    let mut stdout = io::stdout();
+    let mut buf = [0_u8; 4096];
+    loop {
+        match master_reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                if !stdout_closed {
+                    if let Err(e) = stdout.write_all(&buf[..n]) {
+                        if e.raw_os_error() == Some(libc::EPIPE) {
+                            stdout_closed = true;
+                        } else {
+                            relay_error = Some(e);
+                            break;
+                        }
+                    }
+                }
+            }
+            // The master read errors with EIO once the slave side has no more openers, which is
+            // how we learn that the child has gone away.
+            Err(ref e) if e.raw_os_error() == Some(libc::EIO) => break,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                relay_error = Some(e);
+                break;
+            }
+        }
+    }
+
+    let status = child.wait()?;
+    // The input-forwarding and winsize-watcher threads are blocked in a `read`/`sigwait` call
+    // that may never return; we don't join them and let them die with the process.
+    drop(input_thread);
+
+    match relay_error {
+        Some(e) => Err(e),
+        None => Ok(status),
+    }
+}
+
+fn sigwinch_set() -> libc::sigset_t {
+    unsafe {
+        let mut set = std::mem::zeroed();
+        libc::sigemptyset(&mut set);
+        libc::sigaddset(&mut set, libc::SIGWINCH);
+        set
+    }
+}
+
+/// Block `SIGWINCH` in the calling thread (and any thread spawned after this call, since signal
+/// masks are inherited at thread creation).
+fn block_sigwinch() {
+    let set = sigwinch_set();
+    unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut()) };
+}
+
+/// Undo [`block_sigwinch`], restoring the default disposition. Used in the child after `fork`.
+fn unblock_sigwinch() {
+    let set = sigwinch_set();
+    unsafe { libc::pthread_sigmask(libc::SIG_UNBLOCK, &set, std::ptr::null_mut()) };
+}
+
+/// Block until `SIGWINCH` is delivered to this process. Returns `false` if waiting on the signal
+/// itself failed, in which case the caller should stop watching for resizes.
+fn wait_for_sigwinch() -> bool {
+    let set = sigwinch_set();
+    let mut signal = 0;
+    unsafe { libc::sigwait(&set, &mut signal) == 0 }
+}
@@ -0,0 +1,8 @@
+//! Terminal handling: reading and copying `termios` settings, raw mode, and pseudo-terminal
+//! allocation.
+
+mod ops;
+mod pty;
+
+pub use ops::{term_copy, term_raw, term_restore, term_set_winsize, RawModeGuard};
+pub use pty::{open_pty, Pty};
@@ -49,11 +49,61 @@ const LOCAL_FLAGS: tcflag_t = ISIG
     | ECHOKE
     | PENDIN;
 
-// FIXME: me no like `static mut`.
-static mut OTERM: MaybeUninit<termios> = MaybeUninit::uninit();
-static CHANGED: AtomicBool = AtomicBool::new(false);
 static GOT_SIGTTOU: AtomicBool = AtomicBool::new(false);
 
+/// The original `termios` of a terminal, captured so it can be restored later.
+///
+/// Unlike the `static mut OTERM` this replaces, a `TermState` is tied to a specific file
+/// descriptor and to the scope that created it, so saving the settings of two terminals (e.g. the
+/// real terminal and a PTY slave) at the same time no longer corrupts either of them.
+struct TermState<F: AsRawFd> {
+    fd: F,
+    original: termios,
+}
+
+impl<F: AsRawFd> TermState<F> {
+    fn save(fd: F) -> io::Result<Self> {
+        let mut original = MaybeUninit::<termios>::uninit();
+        cerr(unsafe { tcgetattr(fd.as_raw_fd(), original.as_mut_ptr()) })?;
+
+        Ok(Self {
+            fd,
+            original: unsafe { original.assume_init() },
+        })
+    }
+
+    fn restore(&self, flush: bool) -> io::Result<()> {
+        let flags = if flush { TCSAFLUSH } else { TCSADRAIN };
+        tcsetattr_nobg(self.fd.as_raw_fd(), flags, &self.original)
+    }
+}
+
+/// Restores a terminal's original settings when dropped, so that raw mode can never be left on
+/// past the scope that enabled it, even if the caller returns early or panics.
+pub struct RawModeGuard<F: AsRawFd> {
+    state: Option<TermState<F>>,
+}
+
+impl<F: AsRawFd> RawModeGuard<F> {
+    /// Restore the original terminal settings now rather than waiting for `Drop`, choosing whether
+    /// to discard queued input via `flush` (mirroring the old `term_restore`'s parameter).
+    pub fn restore(mut self, flush: bool) -> io::Result<()> {
+        match self.state.take() {
+            Some(state) => state.restore(flush),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<F: AsRawFd> Drop for RawModeGuard<F> {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.take() {
+            // Best effort: there is nothing useful to do with an error while dropping.
+            let _ = state.restore(false);
+        }
+    }
+}
+
 extern "C" fn on_sigttou(_signal: c_int, _info: *mut siginfo_t, _: *mut c_void) {
     GOT_SIGTTOU.store(true, Ordering::SeqCst);
 }
@@ -150,38 +200,48 @@ pub fn term_copy<S: AsRawFd, D: AsRawFd>(src: &S, dst: &D) -> io::Result<()> {
     Ok(())
 }
 
-/// Set the `fd` terminal to raw mode. Enable terminal signals if `with_signals` is set to `true`.  
-pub fn term_raw<F: AsRawFd>(fd: &F, with_signals: bool) -> io::Result<()> {
-    let fd = fd.as_raw_fd();
+/// Copy just the window size of the `src` terminal to the `dst` terminal.
+///
+/// Unlike [`term_copy`], which is meant to be called once to set up a new terminal, this only
+/// touches `TIOCGWINSZ`/`TIOCSWINSZ` so it is cheap enough to call every time `src` receives a
+/// `SIGWINCH`.
+pub fn term_set_winsize<S: AsRawFd, D: AsRawFd>(src: &S, dst: &D) -> io::Result<()> {
+    let src = src.as_raw_fd();
+    let dst = dst.as_raw_fd();
+
+    let mut wsize = MaybeUninit::<winsize>::uninit();
+
+    cerr(unsafe { ioctl(src, TIOCGWINSZ, wsize.as_mut_ptr()) })?;
+    cerr(unsafe { ioctl(dst, TIOCSWINSZ, wsize.as_ptr()) })?;
+
+    Ok(())
+}
+
+/// Set the `fd` terminal to raw mode. Enable terminal signals if `with_signals` is set to `true`.
+///
+/// Returns a [`RawModeGuard`] that restores `fd`'s original settings when it is dropped (or when
+/// [`term_restore`] is called on it explicitly), so raw mode is never left on past the scope that
+/// requested it.
+pub fn term_raw<F: AsRawFd>(fd: F, with_signals: bool) -> io::Result<RawModeGuard<F>> {
+    let state = TermState::save(fd)?;
 
-    if !CHANGED.load(Ordering::Acquire) {
-        cerr(unsafe { tcgetattr(fd, OTERM.as_mut_ptr()) })?;
-    }
-    // Retrieve the original terminal.
-    let mut term = unsafe { OTERM.assume_init() };
     // Set terminal to raw mode.
+    let mut term = state.original;
     unsafe { cfmakeraw(&mut term) };
     // Enable terminal signals.
     if with_signals {
         term.c_cflag |= ISIG;
     }
 
-    tcsetattr_nobg(fd, TCSADRAIN, &term)?;
-    CHANGED.store(true, Ordering::Release);
+    tcsetattr_nobg(state.fd.as_raw_fd(), TCSADRAIN, &term)?;
 
-    Ok(())
+    Ok(RawModeGuard { state: Some(state) })
 }
 
-/// Restore the saved terminal settings if we are in the foreground process group.
+/// Restore the settings a [`RawModeGuard`] saved, if we are in the foreground process group.
 ///
 /// This change is done after waiting for all the queued output to be written. To discard the
 /// queued input `flush` must be set to `true`.
-pub fn term_restore<F: AsRawFd>(fd: &F, flush: bool) -> io::Result<()> {
-    if CHANGED.load(Ordering::Acquire) {
-        let fd = fd.as_raw_fd();
-        let flags = if flush { TCSAFLUSH } else { TCSADRAIN };
-        tcsetattr_nobg(fd, flags, unsafe { OTERM.as_ptr() })?;
-    }
-
-    Ok(())
+pub fn term_restore<F: AsRawFd>(guard: RawModeGuard<F>, flush: bool) -> io::Result<()> {
+    guard.restore(flush)
 }
@@ -0,0 +1,51 @@
+//! Allocation of a pseudo-terminal pair, used by `su --pty` to give the target command a
+//! controlling terminal that is isolated from the invoking user's own terminal.
+
+use std::ffi::CStr;
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+
+use crate::cutils::cerr;
+
+/// The master and slave ends of a freshly allocated pseudo-terminal.
+pub struct Pty {
+    pub master: File,
+    pub slave: File,
+}
+
+/// Open a new pseudo-terminal pair via `posix_openpt`/`grantpt`/`unlockpt`.
+///
+/// The returned master and slave are plain files; callers are expected to use
+/// [`super::term_copy`] to clone the real terminal's settings and window size onto the slave
+/// before handing it to the child process.
+pub fn open_pty() -> io::Result<Pty> {
+    let master_fd = cerr(unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY) })?;
+    // SAFETY: `posix_openpt` returned a valid, owned file descriptor.
+    let master = unsafe { OwnedFd::from_raw_fd(master_fd) };
+
+    cerr(unsafe { libc::grantpt(master.as_raw_fd()) })?;
+    cerr(unsafe { libc::unlockpt(master.as_raw_fd()) })?;
+
+    let slave_path = ptsname(master.as_raw_fd())?;
+    let slave = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&slave_path)?;
+
+    Ok(Pty {
+        // SAFETY: `master` owns a valid, open file descriptor; `File` takes over that ownership.
+        master: unsafe { File::from_raw_fd(master.into_raw_fd()) },
+        slave,
+    })
+}
+
+fn ptsname(fd: RawFd) -> io::Result<String> {
+    let mut buf = [0_i8; 4096];
+
+    cerr(unsafe { libc::ptsname_r(fd, buf.as_mut_ptr(), buf.len()) })?;
+
+    let name = unsafe { CStr::from_ptr(buf.as_ptr()) };
+
+    Ok(name.to_string_lossy().into_owned())
+}